@@ -0,0 +1,66 @@
+/// Tile payload formats the server knows how to label with a content type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    Png,
+    Jpg,
+    Webp,
+    Pbf,
+}
+
+impl TileFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            TileFormat::Png => "image/png",
+            TileFormat::Jpg => "image/jpeg",
+            TileFormat::Webp => "image/webp",
+            TileFormat::Pbf => "application/x-protobuf",
+        }
+    }
+
+    /// Short format name as used in TileJSON's `format` field.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            TileFormat::Png => "png",
+            TileFormat::Jpg => "jpg",
+            TileFormat::Webp => "webp",
+            TileFormat::Pbf => "pbf",
+        }
+    }
+
+    /// Maps a file extension (without the leading dot, and without a
+    /// trailing `.gz`) to a format.
+    pub fn from_extension(ext: &str) -> Option<TileFormat> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(TileFormat::Png),
+            "jpg" | "jpeg" => Some(TileFormat::Jpg),
+            "webp" => Some(TileFormat::Webp),
+            "pbf" | "mvt" => Some(TileFormat::Pbf),
+            _ => None,
+        }
+    }
+
+    /// Maps an MBTiles `metadata.format` value (`png`, `jpg`, `webp`, `pbf`) to a format.
+    pub fn from_metadata_str(value: &str) -> Option<TileFormat> {
+        TileFormat::from_extension(value)
+    }
+
+    /// Falls back to sniffing magic bytes when there's no extension or
+    /// metadata to go on.
+    pub fn sniff(data: &[u8]) -> Option<TileFormat> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+            Some(TileFormat::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(TileFormat::Jpg)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(TileFormat::Webp)
+        } else {
+            None
+        }
+    }
+}
+
+/// True when `data` starts with the gzip magic header (`1F 8B`), used to mark
+/// gzip-compressed PBF tiles with a `Content-Encoding: gzip` response header.
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0x1F && data[1] == 0x8B
+}
@@ -1,15 +1,24 @@
-use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{get, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use clap::Parser;
-use std::fs::File;
-use std::io::Read;
-use log::{info, error, warn};
+use log::{info, warn};
 use chrono::{DateTime, Local};
 use flexi_logger::{Cleanup, Criterion, DeferredNow, Naming, Record};
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+mod format;
+mod render;
+mod tile_source;
+mod tilejson;
+mod tileset;
+
+pub use tile_source::{build_tile_source, FileTileSource, MbtilesTileSource, Tile, TileSource};
+pub use tileset::TilesetRegistry;
+use render::RenderPool;
+use tilejson::TileJson;
 
 const ENV_VAR_LOG_DIR: &str = "EXE_UNIT_LOG_DIR";
 const DEFAULT_LOG_LEVEL: &str = "info";
-const DEFAULT_LOG_DIR: &str = "logs";
 const DEFAULT_LOG_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
 
 
@@ -32,13 +41,17 @@ tiles_rs.exe --tiles-dir=C:\\Users\\Tiles --host=0.0.0.0 --port=5000 --log_level
 ```
 \n\
 # Api\n\n\
-- /tiles/{z}/{x}/{y}\n\n\
+- /tiles/{tileset}/{z}/{x}/{y}\n\
+- /services\n\
+- /services/{tileset}\n\
+- /services/{tileset}/map\n\n\
+{tileset} - Name of a tileset discovered under --tiles-dir (subdirectory or .mbtiles file).
 {z} - The current zoom level.
 {x} - The horizontal (X) index of the requested tile.
 {y} - The vertical (Y) index of the requested tile."
 )]
 pub struct Args {
-    /// Directory containing tile images
+    /// Directory containing tile images, or a single .mbtiles file
     #[arg(long, default_value = "Tiles")]
     pub tiles_dir: String,
 
@@ -52,7 +65,43 @@ pub struct Args {
 
     /// Log level (error, warn, info, debug, trace)
     #[arg(long, default_value = "info")]
-    pub log_level: String
+    pub log_level: String,
+
+    /// Allow POST /reload to re-scan --tiles-dir without restarting the server
+    #[arg(long, default_value_t = false)]
+    pub allow_reload: bool,
+
+    /// Disable the GET /services/{tileset}/map preview page
+    #[arg(long, default_value_t = false)]
+    pub disable_preview: bool,
+
+    /// Number of worker threads for on-demand tile rendering (0 disables rendering)
+    #[arg(long, default_value_t = 0)]
+    pub render_threads: usize,
+
+    /// Shell command that renders a missing tile, with {tileset}/{z}/{x}/{y}/{out} placeholders
+    #[arg(long)]
+    pub render_command: Option<String>,
+
+    /// How long a rendered tile stays fresh before being re-rendered lazily, in seconds
+    #[arg(long, default_value_t = 86_400)]
+    pub render_ttl_secs: u64,
+
+    /// Log output format: pretty (local time), rfc3339, or json
+    #[arg(long, default_value = "pretty")]
+    pub log_format: String,
+
+    /// Directory to write log files to; pass "stderr" to log to stderr only
+    #[arg(long, default_value = "logs")]
+    pub log_dir: String,
+
+    /// Rotate a log file once it reaches this size, in bytes
+    #[arg(long, default_value_t = 10_000)]
+    pub log_rotate_size: u64,
+
+    /// Number of rotated log files to keep
+    #[arg(long, default_value_t = 3)]
+    pub log_keep_files: usize,
 }
 
 #[get("/")]
@@ -60,76 +109,213 @@ async fn index() -> impl Responder {
     HttpResponse::Ok().body("<h1>map source</h1>")
 }
 
-#[get("/tiles/{z}/{x}/{y}")]
-async fn get_tiles(path: web::Path<(u32, u32, u32)>, data: web::Data<AppState>) -> impl Responder {
-    let (z, x, y) = path.into_inner();
-    let img_path = format!("{}/{}/{}/{}.png", data.tiles_dir, z, x, y);
-    
-    match File::open(&img_path) {
-        Ok(mut file) => {
-            let mut buffer = Vec::new();
-            if file.read_to_end(&mut buffer).is_ok() {   
-                info!("Serving tile: {}", img_path);             
-                HttpResponse::Ok()
-                    .content_type("image/png")
-                    .body(buffer)
-            } else {
-                error!("Error reading file: {}", img_path);
-                HttpResponse::InternalServerError().finish()
+/// How long to sleep between polls while waiting on an in-flight render.
+const RENDER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[get("/tiles/{tileset}/{z}/{x}/{y}")]
+async fn get_tiles(
+    path: web::Path<(String, u32, u32, u32)>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let (tileset, z, x, y) = path.into_inner();
+
+    let Some(source) = data.registry.get(&tileset) else {
+        warn!("Unknown tileset: {}", tileset);
+        return HttpResponse::NotFound().finish();
+    };
+
+    if let Some(pool) = &data.render_pool {
+        let cache_path = pool.cache_path(&tileset, z, x, y);
+        if pool.is_stale(&cache_path) {
+            pool.ensure_rendering(&tileset, z, x, y);
+        }
+        while pool.is_rendering(&tileset, z, x, y) {
+            actix_web::rt::time::sleep(RENDER_POLL_INTERVAL).await;
+        }
+    }
+
+    match source.get_tile(z, x, y) {
+        Some(tile) => {
+            info!("Serving tile: {}/{}/{}/{}", tileset, z, x, y);
+            let mut response = HttpResponse::Ok();
+            response.content_type(tile.content_type);
+            if let Some(encoding) = tile.content_encoding {
+                response.insert_header(("Content-Encoding", encoding));
             }
+            response.body(tile.data)
         }
-        Err(_) => {
-            warn!("File not found: {}", img_path);
+        None => {
+            warn!("Tile not found: {}/{}/{}/{}", tileset, z, x, y);
             HttpResponse::NotFound().finish()
         }
     }
 }
 
+#[derive(serde::Serialize)]
+struct ServicesResponse {
+    tilesets: Vec<String>,
+}
+
+#[get("/services")]
+async fn services(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(ServicesResponse {
+        tilesets: data.registry.names(),
+    })
+}
+
+/// Builds `scheme://host` from the incoming request, so TileJSON and the
+/// preview page work whether the server is reached via `localhost` or a
+/// reverse-proxied public hostname.
+fn base_url(req: &HttpRequest) -> String {
+    let conn = req.connection_info();
+    format!("{}://{}", conn.scheme(), conn.host())
+}
+
+#[get("/services/{tileset}")]
+async fn tileset_tilejson(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let tileset = path.into_inner();
+    let Some(source) = data.registry.get(&tileset) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let info = source.tile_info();
+    HttpResponse::Ok().json(TileJson::new(&tileset, &base_url(&req), &info))
+}
+
+#[get("/services/{tileset}/map")]
+async fn tileset_map(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if data.disable_preview {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let tileset = path.into_inner();
+    let Some(source) = data.registry.get(&tileset) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let info = source.tile_info();
+    let tile_url = format!("{}/tiles/{}/{{z}}/{{x}}/{{y}}", base_url(&req), tileset);
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(tilejson::render_map_html(&tileset, &tile_url, &info))
+}
+
+#[derive(serde::Serialize)]
+struct ReloadResponse {
+    tilesets_loaded: usize,
+}
+
+#[actix_web::post("/reload")]
+async fn reload(data: web::Data<AppState>) -> impl Responder {
+    if !data.allow_reload {
+        return HttpResponse::Forbidden().body("Reloading is disabled; pass --allow-reload to enable it");
+    }
+
+    match data.registry.reload() {
+        Ok(tilesets_loaded) => {
+            info!("Reloaded tileset registry: {} tileset(s) loaded", tilesets_loaded);
+            HttpResponse::Ok().json(ReloadResponse { tilesets_loaded })
+        }
+        Err(err) => {
+            warn!("Failed to reload tileset registry: {:?}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 pub struct AppState {
-    tiles_dir: String,
+    registry: TilesetRegistry,
+    allow_reload: bool,
+    disable_preview: bool,
+    render_pool: Option<Arc<RenderPool>>,
 }
 
-pub async fn run_server(tiles_dir: String, host: String, port: u16) -> std::io::Result<()> {
-    println!("Server starting on http://{}:{}", host, port);
-    println!("Serving tiles from: {}", tiles_dir);
+pub async fn run_server(args: Args) -> std::io::Result<()> {
+    println!("Server starting on http://{}:{}", args.host, args.port);
+    println!("Serving tiles from: {}", args.tiles_dir);
+
+    let render_pool = match (&args.render_command, args.render_threads) {
+        (Some(command), threads) if threads > 0 => {
+            info!("Render-on-demand enabled with {} worker thread(s)", threads);
+            Some(RenderPool::new(
+                args.tiles_dir.clone(),
+                command.clone(),
+                threads,
+                Duration::from_secs(args.render_ttl_secs),
+            ))
+        }
+        (None, threads) if threads > 0 => {
+            warn!("--render-threads set without --render-command; render-on-demand disabled");
+            None
+        }
+        _ => None,
+    };
+
+    let app_state = web::Data::new(AppState {
+        registry: TilesetRegistry::discover(&args.tiles_dir)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?,
+        allow_reload: args.allow_reload,
+        disable_preview: args.disable_preview,
+        render_pool,
+    });
 
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(AppState {
-                tiles_dir: tiles_dir.clone(),
-            }))
+            .app_data(app_state.clone())
             .service(index)
             .service(get_tiles)
+            .service(services)
+            .service(tileset_tilejson)
+            .service(tileset_map)
+            .service(reload)
     })
-    .bind((host, port))?
+    .bind((args.host, args.port))?
     .run()
     .await
 }
 
 // https://github.com/golemfactory/yagna/blob/master/exe-unit/src/logger.rs#L13
 pub fn start_file_logger(args: &Args) -> anyhow::Result<flexi_logger::LoggerHandle> {
-    let log_dir = std::env::var(ENV_VAR_LOG_DIR).unwrap_or_else(|_| DEFAULT_LOG_DIR.to_string());
+    // "stderr" is a destination, not a real directory, so route straight to
+    // the stderr-only logger instead of trying to create a directory for it.
+    if args.log_dir.eq_ignore_ascii_case("stderr") {
+        return start_logger(args);
+    }
 
-    Ok(build_logger(Some(&args.log_level))?
+    let log_dir = std::env::var(ENV_VAR_LOG_DIR).unwrap_or_else(|_| args.log_dir.clone());
+
+    Ok(build_logger(&args.log_level, &args.log_format)?
         .log_to_file(flexi_logger::FileSpec::default().directory(log_dir))
         .duplicate_to_stderr(log_tty_dup_level()?)
         .rotate(
-            Criterion::Size(10_000), // 设置日志文件大小限制为 5 KB
-            Naming::Timestamps,         // 使用时间戳进行文件命名
-            Cleanup::KeepLogFiles(3),   // 保留最近的 3 个日志文件
+            Criterion::Size(args.log_rotate_size),
+            Naming::Timestamps,
+            Cleanup::KeepLogFiles(args.log_keep_files),
         )
         .start()?)
 }
 
-fn build_logger<S: ToString>(log_level: Option<S>) -> anyhow::Result<flexi_logger::Logger> {
-    let level = match log_level {
-        Some(level) => level.to_string(),
-        None => std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_LEVEL.to_string()),
-    };
-
-    Ok(flexi_logger::Logger::try_with_str(level)?
-        .format(log_format)
-        .format_for_stderr(flexi_logger::colored_opt_format))
+fn build_logger(log_level: &str, format: &str) -> anyhow::Result<flexi_logger::Logger> {
+    let logger = flexi_logger::Logger::try_with_str(log_level)?;
+    Ok(match format {
+        "json" => logger
+            .format(log_format_json)
+            .format_for_stderr(log_format_json),
+        "rfc3339" => logger
+            .format(log_format_rfc3339)
+            .format_for_stderr(log_format_rfc3339),
+        _ => logger
+            .format(log_format_pretty)
+            .format_for_stderr(flexi_logger::colored_opt_format),
+    })
 }
 
 fn log_tty_dup_level() -> anyhow::Result<flexi_logger::Duplicate> {
@@ -153,11 +339,13 @@ fn log_tty_dup_level() -> anyhow::Result<flexi_logger::Duplicate> {
     })
 }
 
-pub fn start_logger() -> anyhow::Result<flexi_logger::LoggerHandle> {
-    Ok(build_logger(Option::<String>::None)?.start()?)
+/// Stderr-only logger, used when `--log-dir=stderr` is requested and as the
+/// fallback when `start_file_logger` fails outright.
+pub fn start_logger(args: &Args) -> anyhow::Result<flexi_logger::LoggerHandle> {
+    Ok(build_logger(&args.log_level, &args.log_format)?.start()?)
 }
 
-fn log_format(
+fn log_format_pretty(
     w: &mut dyn std::io::Write,
     now: &mut DeferredNow,
     record: &Record,
@@ -176,4 +364,42 @@ fn log_format(
         record.module_path().unwrap_or("<unnamed>"),
         record.args()
     )
+}
+
+fn log_format_rfc3339(
+    w: &mut dyn std::io::Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    let now = SystemTime::from(*now.now());
+    let local_date = DateTime::<Local>::from(now);
+
+    write!(
+        w,
+        "[{} {:5} {}] {}",
+        local_date.to_rfc3339(),
+        record.level(),
+        record.module_path().unwrap_or("<unnamed>"),
+        record.args()
+    )
+}
+
+/// Emits one JSON object per line (timestamp, level, module, message) so
+/// logs can be ingested by log aggregators.
+fn log_format_json(
+    w: &mut dyn std::io::Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> Result<(), std::io::Error> {
+    let now = SystemTime::from(*now.now());
+    let local_date = DateTime::<Local>::from(now);
+
+    let entry = serde_json::json!({
+        "timestamp": local_date.to_rfc3339(),
+        "level": record.level().to_string(),
+        "module": record.module_path().unwrap_or("<unnamed>"),
+        "message": record.args().to_string(),
+    });
+
+    write!(w, "{}", entry)
 }
\ No newline at end of file
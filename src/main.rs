@@ -7,9 +7,9 @@ async fn main() -> std::io::Result<()> {
 
      // Setup logger
      if let Err(error) = start_file_logger(&args) {
-        start_logger().expect("Failed to start logging");
+        start_logger(&args).expect("Failed to start logging");
         log::warn!("Using fallback logging due to an error: {:?}", error);
     };
 
-    run_server(args.tiles_dir, args.host, args.port).await
+    run_server(args).await
 }
\ No newline at end of file
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crossbeam_channel::{unbounded, Sender};
+use log::{error, info};
+use parking_lot::RwLock;
+
+type TileKey = (String, u32, u32, u32);
+
+struct RenderJob {
+    tileset: String,
+    z: u32,
+    x: u32,
+    y: u32,
+}
+
+/// Render-on-demand subsystem: when `get_tiles` misses its cache, it asks the
+/// pool to render the tile instead of returning 404. A bounded pool of
+/// worker threads pulls jobs off a `crossbeam_channel` queue, invokes an
+/// external rasterizer command, and writes the result to
+/// `{tiles_dir}/{tileset}/{z}/{x}/{y}.png`, from where a directory tileset
+/// serves it on later requests. `.mbtiles` tilesets are served from their
+/// database and never read these rendered files.
+///
+/// Concurrent requests for the same tile are coalesced through `in_flight`:
+/// the first request records the tile as being rendered, later requests for
+/// the same key see it there and just wait for the render to finish instead
+/// of enqueueing a duplicate job.
+pub struct RenderPool {
+    sender: Sender<RenderJob>,
+    in_flight: RwLock<HashMap<TileKey, SystemTime>>,
+    tiles_dir: String,
+    command: String,
+    ttl: Duration,
+}
+
+impl RenderPool {
+    pub fn new(tiles_dir: String, command: String, threads: usize, ttl: Duration) -> Arc<Self> {
+        let (sender, receiver) = unbounded::<RenderJob>();
+        let pool = Arc::new(RenderPool {
+            sender,
+            in_flight: RwLock::new(HashMap::new()),
+            tiles_dir,
+            command,
+            ttl,
+        });
+
+        for _ in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                for job in receiver.iter() {
+                    pool.render(job);
+                }
+            });
+        }
+
+        pool
+    }
+
+    /// Path a rendered tile is written to / read back from.
+    pub fn cache_path(&self, tileset: &str, z: u32, x: u32, y: u32) -> String {
+        format!("{}/{}/{}/{}/{}.png", self.tiles_dir, tileset, z, x, y)
+    }
+
+    /// True when the tile at `path` is missing or older than the configured TTL.
+    pub fn is_stale(&self, path: &str) -> bool {
+        match std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified.elapsed().map(|age| age > self.ttl).unwrap_or(false),
+            Err(_) => true,
+        }
+    }
+
+    /// True while a render for this tile is in progress.
+    pub fn is_rendering(&self, tileset: &str, z: u32, x: u32, y: u32) -> bool {
+        self.in_flight
+            .read()
+            .contains_key(&(tileset.to_string(), z, x, y))
+    }
+
+    /// Enqueues a render job unless one for this tile is already in flight.
+    pub fn ensure_rendering(&self, tileset: &str, z: u32, x: u32, y: u32) {
+        let key = (tileset.to_string(), z, x, y);
+        let mut in_flight = self.in_flight.write();
+        if in_flight.contains_key(&key) {
+            return;
+        }
+        in_flight.insert(key, SystemTime::now());
+        drop(in_flight);
+
+        let _ = self.sender.send(RenderJob {
+            tileset: tileset.to_string(),
+            z,
+            x,
+            y,
+        });
+    }
+
+    fn render(&self, job: RenderJob) {
+        let out_path = self.cache_path(&job.tileset, job.z, job.x, job.y);
+        let dir = format!("{}/{}/{}/{}", self.tiles_dir, job.tileset, job.z, job.x);
+
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            error!("Failed to create tile cache dir {}: {}", dir, err);
+        } else {
+            let command = self
+                .command
+                .replace("{tileset}", &job.tileset)
+                .replace("{z}", &job.z.to_string())
+                .replace("{x}", &job.x.to_string())
+                .replace("{y}", &job.y.to_string())
+                .replace("{out}", &out_path);
+
+            match run_command(&command) {
+                Ok(status) if status.success() => {
+                    info!("Rendered tile {}/{}/{}/{}", job.tileset, job.z, job.x, job.y);
+                }
+                Ok(status) => error!("Render command `{}` exited with {}", command, status),
+                Err(err) => error!("Failed to run render command `{}`: {}", command, err),
+            }
+        }
+
+        self.in_flight
+            .write()
+            .remove(&(job.tileset, job.z, job.x, job.y));
+    }
+}
+
+fn run_command(command: &str) -> std::io::Result<ExitStatus> {
+    if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", command]).status()
+    } else {
+        Command::new("sh").args(["-c", command]).status()
+    }
+}
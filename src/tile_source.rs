@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+use actix_web::web::Bytes;
+
+use crate::format::{is_gzip, TileFormat};
+
+/// A resolved tile: the raw bytes plus the headers to serve them with.
+pub struct Tile {
+    pub data: Bytes,
+    pub content_type: String,
+    pub content_encoding: Option<&'static str>,
+}
+
+impl Tile {
+    fn new(data: Vec<u8>, format: TileFormat, gzipped: bool) -> Self {
+        Tile {
+            data: Bytes::from(data),
+            content_type: format.content_type().to_string(),
+            content_encoding: if gzipped { Some("gzip") } else { None },
+        }
+    }
+}
+
+/// Bounds and zoom summary used to build TileJSON for a tileset.
+pub struct TileInfo {
+    pub format: TileFormat,
+    pub minzoom: u32,
+    pub maxzoom: u32,
+    /// `[west, south, east, north]` in WGS84 degrees.
+    pub bounds: [f64; 4],
+    /// `[longitude, latitude, zoom]`.
+    pub center: [f64; 3],
+}
+
+/// Full-world bounds, used whenever a tileset doesn't specify its own.
+const WORLD_BOUNDS: [f64; 4] = [-180.0, -85.0511, 180.0, 85.0511];
+
+impl Default for TileInfo {
+    fn default() -> Self {
+        TileInfo {
+            format: TileFormat::Png,
+            minzoom: 0,
+            maxzoom: 19,
+            bounds: WORLD_BOUNDS,
+            center: [0.0, 0.0, 2.0],
+        }
+    }
+}
+
+/// Abstraction over where tile bytes come from, so `AppState` can be backed
+/// by a directory of `{z}/{x}/{y}.{ext}` files or by a single MBTiles database.
+pub trait TileSource: Send + Sync {
+    fn get_tile(&self, z: u32, x: u32, y: u32) -> Option<Tile>;
+
+    /// Bounds/zoom/format summary used to generate TileJSON. Implementors
+    /// that have no such metadata may fall back to `TileInfo::default()`.
+    fn tile_info(&self) -> TileInfo;
+}
+
+/// Extensions probed, in order, under `{tiles_dir}/{z}/{x}/{y}.*` — covers
+/// raster formats plus plain and gzip-compressed vector tiles.
+const CANDIDATE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "pbf", "pbf.gz", "mvt", "mvt.gz"];
+
+/// Serves tiles from `{tiles_dir}/{z}/{x}/{y}.{ext}` on disk, detecting the
+/// actual format from whichever extension is present.
+pub struct FileTileSource {
+    pub tiles_dir: String,
+}
+
+impl TileSource for FileTileSource {
+    fn get_tile(&self, z: u32, x: u32, y: u32) -> Option<Tile> {
+        for ext in CANDIDATE_EXTENSIONS {
+            let img_path = format!("{}/{}/{}/{}.{}", self.tiles_dir, z, x, y, ext);
+            let mut file = match File::open(&img_path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            let mut buffer = Vec::new();
+            if file.read_to_end(&mut buffer).is_err() {
+                continue;
+            }
+            let base_ext = ext.trim_end_matches(".gz");
+            let gzipped = ext.ends_with(".gz") || is_gzip(&buffer);
+            let format = TileFormat::from_extension(base_ext)
+                .or_else(|| TileFormat::sniff(&buffer))
+                .unwrap_or(TileFormat::Png);
+            return Some(Tile::new(buffer, format, gzipped));
+        }
+        None
+    }
+
+    fn tile_info(&self) -> TileInfo {
+        let zoom_levels: Vec<u32> = std::fs::read_dir(&self.tiles_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| entry.file_name().to_str().and_then(|n| n.parse().ok()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        match (zoom_levels.iter().min(), zoom_levels.iter().max()) {
+            (Some(&minzoom), Some(&maxzoom)) => TileInfo {
+                minzoom,
+                maxzoom,
+                ..TileInfo::default()
+            },
+            _ => TileInfo::default(),
+        }
+    }
+}
+
+/// Serves tiles from a single MBTiles (SQLite) database.
+///
+/// MBTiles stores rows in TMS order (origin at the bottom-left), while the
+/// `/tiles/{z}/{x}/{y}` API follows the XYZ convention (origin at the
+/// top-left), so the row index is flipped before querying.
+pub struct MbtilesTileSource {
+    conn: Mutex<rusqlite::Connection>,
+    format: TileFormat,
+}
+
+impl MbtilesTileSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        let format = Self::read_format(&conn).unwrap_or(TileFormat::Png);
+        Ok(MbtilesTileSource {
+            conn: Mutex::new(conn),
+            format,
+        })
+    }
+
+    fn read_format(conn: &rusqlite::Connection) -> Option<TileFormat> {
+        let value: String = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE name = 'format'",
+                [],
+                |row| row.get(0),
+            )
+            .ok()?;
+        TileFormat::from_metadata_str(&value)
+    }
+
+    /// Reads a single value out of the MBTiles `metadata` table (e.g.
+    /// `format`, `bounds`, `minzoom`, `maxzoom`).
+    pub fn metadata_value(&self, key: &str) -> Option<String> {
+        let conn = self.conn.lock().ok()?;
+        conn.query_row(
+            "SELECT value FROM metadata WHERE name = ?1",
+            rusqlite::params![key],
+            |row| row.get(0),
+        )
+        .ok()
+    }
+}
+
+impl TileSource for MbtilesTileSource {
+    fn get_tile(&self, z: u32, x: u32, y: u32) -> Option<Tile> {
+        let tms_y = (1u32 << z).checked_sub(1)?.checked_sub(y)?;
+        let conn = self.conn.lock().ok()?;
+        let data: Vec<u8> = conn
+            .query_row(
+                "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                rusqlite::params![z, x, tms_y],
+                |row| row.get(0),
+            )
+            .ok()?;
+        let gzipped = is_gzip(&data);
+        Some(Tile::new(data, self.format, gzipped))
+    }
+
+    fn tile_info(&self) -> TileInfo {
+        let default = TileInfo::default();
+        let minzoom = self
+            .metadata_value("minzoom")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default.minzoom);
+        let maxzoom = self
+            .metadata_value("maxzoom")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default.maxzoom);
+        let bounds = self
+            .metadata_value("bounds")
+            .and_then(|v| parse_csv_f64::<4>(&v))
+            .unwrap_or(default.bounds);
+        let center = self
+            .metadata_value("center")
+            .and_then(|v| parse_csv_f64::<3>(&v))
+            .unwrap_or(default.center);
+
+        TileInfo {
+            format: self.format,
+            minzoom,
+            maxzoom,
+            bounds,
+            center,
+        }
+    }
+}
+
+/// Parses a comma-separated list of floats (MBTiles `bounds`/`center` values)
+/// into a fixed-size array, failing if the count doesn't match.
+fn parse_csv_f64<const N: usize>(value: &str) -> Option<[f64; N]> {
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    parts.try_into().ok()
+}
+
+/// Picks a `TileSource` implementation based on whether `tiles_dir` points at
+/// a directory (`FileTileSource`) or at an `.mbtiles` file (`MbtilesTileSource`).
+pub fn build_tile_source(tiles_dir: &str) -> anyhow::Result<Box<dyn TileSource>> {
+    let path = Path::new(tiles_dir);
+    if path.extension().and_then(|ext| ext.to_str()) == Some("mbtiles") {
+        Ok(Box::new(MbtilesTileSource::open(path)?))
+    } else {
+        Ok(Box::new(FileTileSource {
+            tiles_dir: tiles_dir.to_string(),
+        }))
+    }
+}
@@ -0,0 +1,66 @@
+use crate::tile_source::TileInfo;
+
+/// TileJSON 3.0 document describing a single tileset.
+/// See https://github.com/mapbox/tilejson-spec/tree/master/3.0.0
+#[derive(serde::Serialize)]
+pub struct TileJson {
+    pub tilejson: &'static str,
+    pub name: String,
+    pub format: &'static str,
+    pub tiles: Vec<String>,
+    pub minzoom: u32,
+    pub maxzoom: u32,
+    pub bounds: [f64; 4],
+    pub center: [f64; 3],
+}
+
+impl TileJson {
+    pub fn new(name: &str, base_url: &str, info: &TileInfo) -> Self {
+        TileJson {
+            tilejson: "3.0.0",
+            name: name.to_string(),
+            format: info.format.short_name(),
+            tiles: vec![format!("{}/tiles/{}/{{z}}/{{x}}/{{y}}", base_url, name)],
+            minzoom: info.minzoom,
+            maxzoom: info.maxzoom,
+            bounds: info.bounds,
+            center: info.center,
+        }
+    }
+}
+
+/// Renders a minimal self-contained Leaflet page previewing `name`, pointed
+/// at this server's own `/tiles/{name}/{z}/{x}/{y}` endpoint.
+pub fn render_map_html(name: &str, tile_url: &str, info: &TileInfo) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>{name} preview</title>
+  <link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css" />
+  <style>html, body, #map {{ height: 100%; margin: 0; }}</style>
+</head>
+<body>
+  <div id="map"></div>
+  <script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+  <script>
+    var map = L.map('map').setView([{center_lat}, {center_lon}], {center_zoom});
+    L.tileLayer('{tile_url}', {{
+      minZoom: {minzoom},
+      maxZoom: {maxzoom},
+      attribution: '{name}'
+    }}).addTo(map);
+  </script>
+</body>
+</html>
+"#,
+        name = name,
+        tile_url = tile_url,
+        center_lon = info.center[0],
+        center_lat = info.center[1],
+        center_zoom = info.center[2],
+        minzoom = info.minzoom,
+        maxzoom = info.maxzoom,
+    )
+}
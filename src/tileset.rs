@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::tile_source::{build_tile_source, TileSource};
+
+/// Returns the name a discovered entry should be registered under: a
+/// subdirectory's own name, or an `.mbtiles` file's stem.
+fn tileset_name(path: &Path) -> Option<String> {
+    if path.is_dir() {
+        path.file_name().and_then(|n| n.to_str()).map(String::from)
+    } else if path.extension().and_then(|ext| ext.to_str()) == Some("mbtiles") {
+        path.file_stem().and_then(|n| n.to_str()).map(String::from)
+    } else {
+        None
+    }
+}
+
+fn open_entry(path: &Path) -> Option<Arc<dyn TileSource>> {
+    let source = build_tile_source(path.to_str()?).ok()?;
+    Some(Arc::from(source))
+}
+
+fn scan(tiles_dir: &str) -> anyhow::Result<HashMap<String, Arc<dyn TileSource>>> {
+    let root = Path::new(tiles_dir);
+    let mut tilesets = HashMap::new();
+
+    // `--tiles-dir` may itself point at a single `.mbtiles` file rather than
+    // a directory of tilesets; register it under its own stem in that case.
+    if root.is_file() {
+        if let (Some(name), Some(source)) = (tileset_name(root), open_entry(root)) {
+            tilesets.insert(name, source);
+        }
+        return Ok(tilesets);
+    }
+
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        if let (Some(name), Some(source)) = (tileset_name(&path), open_entry(&path)) {
+            tilesets.insert(name, source);
+        }
+    }
+    Ok(tilesets)
+}
+
+/// The in-memory set of named tilesets discovered under `--tiles-dir`.
+///
+/// Reads go through a lock-free `ArcSwap` snapshot so that `/reload` can
+/// atomically replace the whole set (tilesets added or removed on disk)
+/// without blocking concurrent tile requests.
+pub struct TilesetRegistry {
+    tiles_dir: String,
+    tilesets: ArcSwap<HashMap<String, Arc<dyn TileSource>>>,
+}
+
+impl TilesetRegistry {
+    pub fn discover(tiles_dir: &str) -> anyhow::Result<Self> {
+        let tilesets = scan(tiles_dir)?;
+        Ok(TilesetRegistry {
+            tiles_dir: tiles_dir.to_string(),
+            tilesets: ArcSwap::from_pointee(tilesets),
+        })
+    }
+
+    /// Re-scans `tiles_dir` and atomically swaps in the new tileset set.
+    /// Returns the number of tilesets now loaded.
+    pub fn reload(&self) -> anyhow::Result<usize> {
+        let tilesets = scan(&self.tiles_dir)?;
+        let count = tilesets.len();
+        self.tilesets.store(Arc::new(tilesets));
+        Ok(count)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn TileSource>> {
+        self.tilesets.load().get(name).cloned()
+    }
+
+    /// Names of all currently loaded tilesets, sorted for stable output.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tilesets.load().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}